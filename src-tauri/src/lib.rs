@@ -6,10 +6,16 @@ use tauri::menu::{Menu, MenuItem};
 use tauri::tray::TrayIconBuilder;
 use serde::{Deserialize, Serialize};
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::sync::Mutex;
 use std::sync::Arc;
+use sha1::{Digest, Sha1};
+
+mod patterns;
+mod store;
 
 #[derive(Serialize, Clone, Debug)]
 struct DriveInfo {
@@ -22,17 +28,518 @@ struct DriveInfo {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct AppConfig {
-    ignored_extensions: Vec<String>,
+    // Gitignore-style patterns describing what to exclude from sync. Compiled into
+    // the `patterns::Matcher` held in AppState (see `rebuild_matcher`).
+    #[serde(default = "default_ignore_patterns")]
+    ignore_patterns: Vec<String>,
+    // When set, every change is captured as a content-addressed, deduplicating
+    // snapshot (see the `store` module) instead of a flat overwrite-in-place copy.
+    #[serde(default)]
+    dedup_backups: bool,
+    // How long a path must stay quiet before we sync it. Coalesces the bursts of
+    // events editors/Finder emit per logical change. Widen it on slow/removable media.
+    #[serde(default = "default_debounce_ms")]
+    debounce_ms: u64,
+}
+
+fn default_debounce_ms() -> u64 {
+    500
+}
+
+fn default_ignore_patterns() -> Vec<String> {
+    vec![
+        // Volatile / lock / partial-download files.
+        "*.plist".into(), "*.log".into(), "*.db".into(), "*.ldb".into(),
+        "*.lock".into(), "*.tmp".into(), "*.temp".into(), "*.crdownload".into(),
+        "*.part".into(), "*.ini".into(), "*.dat".into(), "*.shm".into(), "*.wal".into(),
+        // Hidden files/dirs and common system/build directories.
+        ".*".into(),
+        "Library/".into(), "node_modules/".into(), "target/".into(), "AppData/".into(),
+    ]
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
-            ignored_extensions: vec![
-                "plist".into(), "log".into(), "db".into(), "ldb".into(), 
-                "lock".into(), "tmp".into(), "temp".into(), "crdownload".into(), 
-                "part".into(), "ini".into(), "dat".into(), "shm".into(), "wal".into()
-            ],
+            ignore_patterns: default_ignore_patterns(),
+            dedup_backups: false,
+            debounce_ms: default_debounce_ms(),
+        }
+    }
+}
+
+// Remembers the last-synced state of each source file so we only recompute a
+// content hash when the file's length or modification time actually moved.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct SyncCacheEntry {
+    len: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    hash: String,
+    // Wall-clock second in which this entry was last written out. Used to detect
+    // "second ambiguity": if a file was modified in the same second we synced it,
+    // an equal mtime cannot prove the file is unchanged.
+    #[serde(default)]
+    synced_at_secs: u64,
+}
+
+// Whether a stored modification time can be trusted to mean "unchanged". Modeled
+// on dirstate-v2's TruncatedTimestamp: an mtime is only reliable when it carries
+// sub-second precision (nanos != 0) and did not land in the same second as our
+// last sync. Otherwise a second write could have slipped in undetected, so the
+// caller must fall back to a content-hash comparison. Shared by the file and
+// directory paths so both reason about precision the same way.
+fn mtime_is_reliable(mtime_secs: u64, mtime_nanos: u32, last_sync_secs: u64) -> bool {
+    mtime_nanos != 0 && mtime_secs != last_sync_secs
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// A timestamp-ordered, collision-free id for a snapshot directory.
+fn snapshot_id() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{:010}.{:09}-{:04}", now.as_secs(), now.subsec_nanos(), n)
+}
+
+// Directory holding the content-addressed store under a backup root.
+fn store_root(backup_root: &str) -> PathBuf {
+    Path::new(backup_root).join("Tether_Store")
+}
+
+// SHA-1 of a file's bytes, streamed in 64 KiB blocks so we never hold a whole
+// file in memory. Mirrors the block size the dirstate status code uses.
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Modification time of a file, in the (secs, nanos) shape we cache.
+fn mtime_parts(meta: &fs::Metadata) -> (u64, u32) {
+    match meta.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()) {
+        Some(d) => (d.as_secs(), d.subsec_nanos()),
+        None => (0, 0),
+    }
+}
+
+// Content hash of a source file, reusing the cached value when its length and
+// mtime are unchanged since the last sync and the mtime is unambiguous, and
+// recomputing (and caching) otherwise.
+fn cached_file_hash(state: &AppState, path: &Path, meta: &fs::Metadata) -> Option<String> {
+    let (secs, nanos) = mtime_parts(meta);
+    let len = meta.len();
+    {
+        let cache = state.sync_cache.lock().unwrap();
+        if let Some(entry) = cache.get(path) {
+            if entry.len == len
+                && entry.mtime_secs == secs
+                && entry.mtime_nanos == nanos
+                && mtime_is_reliable(secs, nanos, entry.synced_at_secs)
+            {
+                return Some(entry.hash.clone());
+            }
+        }
+    }
+    let hash = hash_file(path).ok()?;
+    let mut cache = state.sync_cache.lock().unwrap();
+    cache.insert(
+        path.to_path_buf(),
+        SyncCacheEntry { len, mtime_secs: secs, mtime_nanos: nanos, hash: hash.clone(), synced_at_secs: now_secs() },
+    );
+    Some(hash)
+}
+
+// A process-unique token for temp file names. We avoid a random-number
+// dependency and instead combine the pid, the current sub-second nanos and a
+// monotonic counter, which is collision-free within a single run.
+fn tmp_token() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    format!("{}-{}-{}", std::process::id(), nanos, n)
+}
+
+fn is_temp_name(name: &str) -> bool {
+    name.starts_with('.') && name.contains(".tether-tmp-")
+}
+
+// Copy `src` onto `dest` atomically: write into a temp file in the same
+// directory, flush it to disk, then rename it over the destination in a single
+// syscall so readers only ever observe the old or the new complete file. If the
+// rename fails (e.g. cross-device) we fall back to a plain copy and drop the temp.
+fn atomic_copy(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let parent = dest.parent().unwrap_or_else(|| Path::new("."));
+    let name = dest.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+    let tmp = parent.join(format!(".{}.tether-tmp-{}", name, tmp_token()));
+
+    {
+        let mut reader = fs::File::open(src)?;
+        let mut writer = fs::File::create(&tmp)?;
+        std::io::copy(&mut reader, &mut writer)?;
+        writer.sync_all()?;
+    }
+
+    match fs::rename(&tmp, dest) {
+        Ok(()) => Ok(()),
+        Err(_) => {
+            let result = fs::copy(&tmp, dest).map(|_| ());
+            let _ = fs::remove_file(&tmp);
+            result
+        }
+    }
+}
+
+// Remove stray `.*.tether-tmp-*` files left behind by an interrupted sync,
+// walking the backup tree. Called when a backup destination is chosen.
+fn cleanup_temp_files(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let p = entry.path();
+        if p.is_dir() {
+            cleanup_temp_files(&p);
+        } else if let Some(name) = p.file_name().and_then(|n| n.to_str()) {
+            if is_temp_name(name) {
+                let _ = fs::remove_file(&p);
+            }
+        }
+    }
+}
+
+// A change buffered by the debouncer, waiting for its path to go quiet before we
+// act on it.
+enum PendingAction {
+    Upsert,
+    Remove,
+    Move { from: PathBuf },
+    // Content-addressed mode: capture a snapshot of the whole watched tree. Keyed
+    // on the watched root so a burst of events collapses into a single snapshot.
+    Snapshot,
+}
+
+struct PendingEvent {
+    action: PendingAction,
+    // Whether the first buffered event for this path was a Create. A Create
+    // immediately followed by a Remove means the file never settled, so we drop
+    // the whole entry instead of syncing then deleting.
+    created: bool,
+    last_seen: std::time::Instant,
+}
+
+// Buffer a raw watcher event keyed by path. Repeated Create/Modify events collapse
+// into a single pending Upsert; a Remove cancels a still-pending Create.
+fn enqueue(state: &AppState, path: PathBuf, action: PendingAction, is_create: bool) {
+    let mut pending = state.pending.lock().unwrap();
+    if let (PendingAction::Remove, Some(existing)) = (&action, pending.get(&path)) {
+        if existing.created {
+            pending.remove(&path);
+            return;
+        }
+    }
+    let created = pending.get(&path).map(|e| e.created).unwrap_or(is_create);
+    pending.insert(path, PendingEvent { action, created, last_seen: std::time::Instant::now() });
+}
+
+// Act on every buffered path that has been quiet for at least the debounce window.
+// Runs on the dedicated timer thread so the notify callback itself stays fast.
+fn flush_ready(handle: &tauri::AppHandle) {
+    let state = handle.state::<AppState>();
+    let window = Duration::from_millis(state.config.lock().unwrap().debounce_ms);
+    let watched_root = match state.watched_root.lock().unwrap().clone() {
+        Some(root) => root,
+        None => return,
+    };
+    let dest_root = match state.backup_path.lock().unwrap().clone() {
+        Some(root) => root,
+        None => return,
+    };
+    let backup_folder = Path::new(&dest_root).join("Tether_Backups");
+
+    let ready: Vec<(PathBuf, PendingEvent)> = {
+        let mut pending = state.pending.lock().unwrap();
+        let now = std::time::Instant::now();
+        let keys: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, ev)| now.duration_since(ev.last_seen) >= window)
+            .map(|(k, _)| k.clone())
+            .collect();
+        keys.into_iter().filter_map(|k| pending.remove(&k).map(|ev| (k, ev))).collect()
+    };
+
+    for (path, ev) in ready {
+        match ev.action {
+            PendingAction::Upsert => sync_one(handle, &watched_root, &backup_folder, &path),
+            PendingAction::Remove => remove_backup(handle, &watched_root, &backup_folder, &path),
+            PendingAction::Move { from } => relocate_backup(handle, &watched_root, &backup_folder, &from, &path),
+            PendingAction::Snapshot => {
+                // Deduplication makes repeated snapshots cheap, but the tree walk
+                // is still synchronous, so it runs here on the timer thread — not
+                // in the notify callback — and only once per quiet window.
+                let store = store_root(&dest_root);
+                let matcher = state.matcher.lock().unwrap().clone();
+                match store::snapshot(&store, &watched_root, &matcher, &snapshot_id()) {
+                    Ok(id) => {
+                        let _ = handle.emit("snapshot-created", id);
+                    }
+                    Err(e) => println!("Snapshot failed: {:?}", e),
+                }
+            }
+        }
+    }
+}
+
+// Map a source path to its mirrored location under the backup folder, keeping
+// the path relative to the watched root so the backup reproduces the watched
+// tree instead of flattening everything into one directory.
+fn mirror_dest(watched_root: &Path, backup_folder: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(watched_root) {
+        Ok(rel) => backup_folder.join(rel),
+        Err(_) => backup_folder.join(path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("file"))),
+    }
+}
+
+// Whether a path should be excluded from sync, according to the compiled
+// ignore matcher, which combines the configured patterns with any `.tetherignore`
+// found in the watched root.
+fn is_ignored(state: &AppState, watched_root: &Path, path: &Path) -> bool {
+    let rel = match path.strip_prefix(watched_root) {
+        Ok(rel) => rel.to_string_lossy().to_string(),
+        Err(_) => path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+    };
+    state.matcher.lock().unwrap().is_match(&rel, path.is_dir())
+}
+
+// (Re)compile the ignore matcher from the configured patterns plus the current
+// `.tetherignore` contents. Called at startup, on save_config, and on start_watching.
+fn rebuild_matcher(state: &AppState) {
+    let mut patterns = state.config.lock().unwrap().ignore_patterns.clone();
+    patterns.extend(state.tetherignore.lock().unwrap().iter().cloned());
+    *state.matcher.lock().unwrap() = patterns::Matcher::from_patterns(patterns);
+}
+
+// Sync a single source path into the mirrored backup tree, reusing the content
+// hash / second-ambiguity checks to avoid redundant copies.
+fn sync_one(handle: &tauri::AppHandle, watched_root: &Path, backup_folder: &Path, path: &Path) {
+    let state = handle.state::<AppState>();
+    if is_ignored(&state, watched_root, path) {
+        return;
+    }
+
+    if path.is_file() {
+        let file_name = path.file_name().unwrap();
+        let dest_path = mirror_dest(watched_root, backup_folder, path);
+        if let Some(parent) = dest_path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        if let Ok(src_meta) = path.metadata() {
+            let (secs, nanos) = mtime_parts(&src_meta);
+            let len = src_meta.len();
+
+            // Fast path: if size and mtime match the last sync and the timestamp
+            // is unambiguous, the file is unchanged — but only trust that when the
+            // backup copy is actually present and the right size. Otherwise (drive
+            // switched, copy lost out-of-band, trashed by a spurious Remove) we
+            // must recreate it regardless of what the source-side cache says.
+            let cached = state.sync_cache.lock().unwrap().get(path).cloned();
+            if let Some(entry) = &cached {
+                if entry.len == len
+                    && entry.mtime_secs == secs
+                    && entry.mtime_nanos == nanos
+                    && mtime_is_reliable(secs, nanos, entry.synced_at_secs)
+                    && dest_path.metadata().map(|m| m.len() == len).unwrap_or(false)
+                {
+                    println!("Skipped (unchanged): {:?}", file_name);
+                    return;
+                }
+            }
+
+            // Ambiguous mtime or a genuine change: confirm against the backup's
+            // bytes before deciding to skip. Equal size is only a hint.
+            if dest_path.exists() {
+                if let Ok(dest_meta) = dest_path.metadata() {
+                    if len == dest_meta.len() {
+                        let src_hash = cached_file_hash(&state, path, &src_meta);
+                        if let (Some(src_hash), Ok(dest_hash)) = (src_hash, hash_file(&dest_path)) {
+                            if src_hash == dest_hash {
+                                println!("Skipped (Up to date): {:?}", file_name);
+                                let mut cache = state.sync_cache.lock().unwrap();
+                                cache.insert(
+                                    path.to_path_buf(),
+                                    SyncCacheEntry { len, mtime_secs: secs, mtime_nanos: nanos, hash: src_hash, synced_at_secs: now_secs() },
+                                );
+                                persist_sync_cache(handle);
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        match atomic_copy(path, &dest_path) {
+            Ok(_) => {
+                println!("Synced: {:?}", file_name);
+                if let Ok(meta) = path.metadata() {
+                    let (secs, nanos) = mtime_parts(&meta);
+                    if let Ok(hash) = hash_file(path) {
+                        let mut cache = state.sync_cache.lock().unwrap();
+                        cache.insert(
+                            path.to_path_buf(),
+                            SyncCacheEntry { len: meta.len(), mtime_secs: secs, mtime_nanos: nanos, hash, synced_at_secs: now_secs() },
+                        );
+                        persist_sync_cache(handle);
+                    }
+                }
+                let _ = handle.emit("file-synced", file_name.to_string_lossy().to_string());
+            }
+            Err(e) => {
+                println!("Sync failed: {:?} -> {:?}", file_name, e);
+            }
+        }
+    } else if path.is_dir() {
+        // Directories get the same second-ambiguity treatment: a coarse
+        // (whole-second) mtime can hide a delete+recreate, so we only trust an
+        // unchanged directory when its timestamp is reliable.
+        if let Ok(meta) = path.metadata() {
+            let (secs, nanos) = mtime_parts(&meta);
+            let dest_dir = mirror_dest(watched_root, backup_folder, path);
+            let cached = state.sync_cache.lock().unwrap().get(path).cloned();
+            if let Some(entry) = &cached {
+                // Only skip when the mirrored directory still exists; a removed
+                // backup subtree must be recreated even if the source is unchanged.
+                if entry.mtime_secs == secs
+                    && entry.mtime_nanos == nanos
+                    && mtime_is_reliable(secs, nanos, entry.synced_at_secs)
+                    && dest_dir.is_dir()
+                {
+                    return;
+                }
+            }
+            let _ = fs::create_dir_all(&dest_dir);
+            let mut cache = state.sync_cache.lock().unwrap();
+            cache.insert(
+                path.to_path_buf(),
+                SyncCacheEntry { len: 0, mtime_secs: secs, mtime_nanos: nanos, hash: String::new(), synced_at_secs: now_secs() },
+            );
+            persist_sync_cache(handle);
+        }
+    }
+}
+
+// Handle a removed source path: move its mirrored copy into a `.tether-trash`
+// tombstone folder (preserving the relative path so it can be restored), or
+// delete it outright if the move fails. Emits `file-removed`.
+fn remove_backup(handle: &tauri::AppHandle, watched_root: &Path, backup_folder: &Path, path: &Path) {
+    let state = handle.state::<AppState>();
+    if is_ignored(&state, watched_root, path) {
+        return;
+    }
+
+    let dest = mirror_dest(watched_root, backup_folder, path);
+    if dest.exists() {
+        let trash_root = backup_folder.join(".tether-trash");
+        let tomb = match dest.strip_prefix(backup_folder) {
+            Ok(rel) => trash_root.join(rel),
+            Err(_) => trash_root.join(dest.file_name().unwrap_or_else(|| std::ffi::OsStr::new("file"))),
+        };
+        if let Some(parent) = tomb.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if fs::rename(&dest, &tomb).is_err() {
+            if dest.is_dir() {
+                let _ = fs::remove_dir_all(&dest);
+            } else {
+                let _ = fs::remove_file(&dest);
+            }
+        }
+        let _ = handle.emit("file-removed", path.to_string_lossy().to_string());
+    }
+
+    let mut cache = state.sync_cache.lock().unwrap();
+    cache.remove(path);
+    persist_sync_cache(handle);
+}
+
+// Handle a rename/move: relocate the mirrored copy from the old path to the new
+// one instead of re-copying, and move the cache entry along. Emits `file-moved`.
+fn relocate_backup(handle: &tauri::AppHandle, watched_root: &Path, backup_folder: &Path, from: &Path, to: &Path) {
+    let state = handle.state::<AppState>();
+    let from_dest = mirror_dest(watched_root, backup_folder, from);
+    let to_dest = mirror_dest(watched_root, backup_folder, to);
+
+    if from_dest.exists() {
+        if let Some(parent) = to_dest.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if fs::rename(&from_dest, &to_dest).is_ok() {
+            let mut cache = state.sync_cache.lock().unwrap();
+            if let Some(entry) = cache.remove(from) {
+                cache.insert(to.to_path_buf(), entry);
+            }
+            persist_sync_cache(handle);
+            let _ = handle.emit(
+                "file-moved",
+                (from.to_string_lossy().to_string(), to.to_string_lossy().to_string()),
+            );
+            return;
+        }
+    }
+    // Nothing to relocate (or the rename failed): fall back to syncing the new path.
+    sync_one(handle, watched_root, backup_folder, to);
+}
+
+fn sync_cache_path(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_data_dir().ok().map(|d| d.join("tether_sync_cache.json"))
+}
+
+// Mark the sync cache as needing a write-out. The actual disk write is batched
+// onto the timer thread (see `flush_sync_cache`) so a burst of file events — e.g.
+// unpacking an archive into the watched folder — doesn't rewrite the whole map
+// once per file.
+fn persist_sync_cache(app_handle: &tauri::AppHandle) {
+    app_handle
+        .state::<AppState>()
+        .cache_dirty
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+// Write the sync cache next to tether_config.json if it has pending changes, so
+// hashes survive restarts. Runs on the timer thread, coalescing a burst of
+// events into at most one write per tick.
+fn flush_sync_cache(app_handle: &tauri::AppHandle) {
+    let state = app_handle.state::<AppState>();
+    if !state.cache_dirty.swap(false, std::sync::atomic::Ordering::Relaxed) {
+        return;
+    }
+    if let Some(path) = sync_cache_path(app_handle) {
+        let cache = state.sync_cache.lock().unwrap();
+        if let Ok(json) = serde_json::to_string_pretty(&*cache) {
+            let _ = fs::write(path, json);
         }
     }
 }
@@ -56,6 +563,17 @@ struct AppState {
     watcher: Mutex<Option<RecommendedWatcher>>,
     backup_path: Mutex<Option<String>>,
     config: Mutex<AppConfig>,
+    sync_cache: Mutex<HashMap<PathBuf, SyncCacheEntry>>,
+    // Set whenever the sync cache changes; the timer thread writes it to disk on
+    // its next tick so a burst of events costs one write, not one write per file.
+    cache_dirty: std::sync::atomic::AtomicBool,
+    // Debouncer state: buffered events keyed by path, plus the root currently
+    // being watched (needed by the timer thread that flushes them).
+    pending: Mutex<HashMap<PathBuf, PendingEvent>>,
+    watched_root: Mutex<Option<PathBuf>>,
+    // Compiled ignore matcher and the raw `.tetherignore` lines it was built from.
+    matcher: Mutex<patterns::Matcher>,
+    tetherignore: Mutex<Vec<String>>,
 }
 
 #[tauri::command]
@@ -66,7 +584,9 @@ fn get_config(state: tauri::State<AppState>) -> AppConfig {
 #[tauri::command]
 fn save_config(config: AppConfig, state: tauri::State<AppState>, app_handle: tauri::AppHandle) -> Result<(), String> {
     *state.config.lock().unwrap() = config.clone();
-    
+    // Recompile the ignore matcher so pattern edits take effect immediately.
+    rebuild_matcher(&state);
+
     // Persist to disk
     let app_dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
     if !app_dir.exists() {
@@ -81,9 +601,30 @@ fn save_config(config: AppConfig, state: tauri::State<AppState>, app_handle: tau
 
 #[tauri::command]
 fn set_backup_path(path: String, state: tauri::State<AppState>) {
+    // Sweep any temp files a previous run may have left behind before we start
+    // writing into this destination.
+    cleanup_temp_files(&Path::new(&path).join("Tether_Backups"));
     *state.backup_path.lock().unwrap() = Some(path);
 }
 
+#[tauri::command]
+fn list_snapshots(state: tauri::State<AppState>) -> Result<Vec<store::SnapshotInfo>, String> {
+    let backup_root = state.backup_path.lock().unwrap().clone().ok_or("No backup path set")?;
+    Ok(store::list_snapshots(&store_root(&backup_root)))
+}
+
+#[tauri::command]
+fn restore_snapshot(id: String, dest: String, state: tauri::State<AppState>) -> Result<(), String> {
+    let backup_root = state.backup_path.lock().unwrap().clone().ok_or("No backup path set")?;
+    store::restore_snapshot(&store_root(&backup_root), &id, Path::new(&dest)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn garbage_collect(state: tauri::State<AppState>) -> Result<usize, String> {
+    let backup_root = state.backup_path.lock().unwrap().clone().ok_or("No backup path set")?;
+    store::garbage_collect(&store_root(&backup_root)).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn start_watching(path: String, app_handle: tauri::AppHandle) -> Result<(), String> {
     let path = Path::new(&path);
@@ -93,96 +634,107 @@ fn start_watching(path: String, app_handle: tauri::AppHandle) -> Result<(), Stri
 
 
     let handle = app_handle.clone();
-    
-    // We need to access state inside the closure, so we grab the state handle.
-    // Note: State<T> is cheap to clone.
-    let state_handle = app_handle.state::<AppState>(); 
-    // However, we can't easily move State into the closure if it strictly references 'r.
-    // Instead we'll access the backup path via the app handle or just use a shared Arc if needed.
-    // For simplicity, let's use the app handle to get the state inside the closure if possible,
-    // or better, wrap the backup_path in an Arc<Mutex> outside the struct if this gets complex.
-    // ACTUALLY: The best way is to clone the Arc inside the Struct if we could, but struct fields are private.
-    // Let's use a standard Arc<Mutex> for the backup path to pass it in.
-    
-    // Re-architecting slightly for thread safety in closure:
-    // We will retrieve the current backup path from the state inside the closure? 
-    // State is not Send/Sync in a way that allows simple moving into a long-running closure?
-    // Let's rely on the handle.
-    
+    let watched_root = path.to_path_buf();
+
+    // Publish the watched root so the debouncer's timer thread can flush events.
+    *app_handle.state::<AppState>().watched_root.lock().unwrap() = Some(watched_root.clone());
+    app_handle.state::<AppState>().pending.lock().unwrap().clear();
+
+    // Pick up a `.tetherignore` in the watched root, if present, and recompile
+    // the ignore matcher to include its patterns.
+    let tetherignore = fs::read_to_string(watched_root.join(".tetherignore"))
+        .map(|c| c.lines().map(|l| l.to_string()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    *app_handle.state::<AppState>().tetherignore.lock().unwrap() = tetherignore;
+    rebuild_matcher(&app_handle.state::<AppState>());
+
     let mut watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
+        use notify::event::{ModifyKind, RenameMode};
         match res {
             Ok(event) => {
-                     match event.kind {
-                         notify::EventKind::Modify(_) | notify::EventKind::Create(_) => {
-                             let paths = event.paths.clone();
-                             let _ = handle.emit("file-changed", &paths);
-                             
-                             // Auto-Sync Logic
-                             let state = handle.state::<AppState>();
-                             let backup_path_guard = state.backup_path.lock().unwrap();
-                             let config_guard = state.config.lock().unwrap(); // Lock config
-                             
-                             if let Some(ref dest_root) = *backup_path_guard {
-                                 let backup_folder = Path::new(dest_root).join("Tether_Backups");
-                                 if !backup_folder.exists() {
-                                     let _ = std::fs::create_dir_all(&backup_folder);
-                                 }
-
-                                 for path in paths {
-                                     // Filter out hidden files and common system directories/files
-                                     if path.components().any(|c| {
-                                         let s = c.as_os_str().to_string_lossy();
-                                         s.starts_with('.') || // Hidden files/dirs (.Trash, .git, .DS_Store)
-                                         s == "Library" || 
-                                         s == "node_modules" || 
-                                         s == "target" ||
-                                         s == "AppData"
-                                     }) {
-                                         continue;
-                                     }
-                                     
-                                     // Filter out ignored extensions from CONFIG
-                                     if let Some(ext) = path.extension() {
-                                         let ext_str = ext.to_string_lossy().to_lowercase();
-                                         if config_guard.ignored_extensions.iter().any(|e| e.to_lowercase() == ext_str) {
-                                            continue;
-                                         }
-                                     }
-
-                                     if path.is_file() {
-                                         let file_name = path.file_name().unwrap();
-                                         let dest_path = backup_folder.join(file_name);
-
-                                         // Smart Sync: Check if file exists and is identical
-                                         if dest_path.exists() {
-                                             if let (Ok(src_meta), Ok(dest_meta)) = (path.metadata(), dest_path.metadata()) {
-                                                 if src_meta.len() == dest_meta.len() {
-                                                     // For now, size match is a strong enough indicator for a quick "resume" check.
-                                                     // You could also check mod times, but size is usually sufficient for checking "did we finish copying?"
-                                                     // or "has the file actually changed content?".
-                                                      println!("Skipped (Up to date): {:?}", file_name);
-                                                      continue;
-                                                 }
-                                             }
-                                         }
-
-                                         match std::fs::copy(&path, &dest_path) {
-                                             Ok(_) => {
-                                                 println!("Synced: {:?}", file_name);
-                                                 let _ = handle.emit("file-synced", file_name.to_string_lossy().to_string());
-                                             },
-                                             Err(e) => {
-                                                  // Only log relevant errors
-                                                  println!("Sync failed: {:?} -> {:?}", file_name, e);
-                                             },
-                                         }
-                                     }
-                                 }
-                             }
-                         }
-                         _ => {}
-                     }
-            },
+                let paths = event.paths.clone();
+
+                // Resolve the current backup destination; nothing to do without one.
+                let dest_root = {
+                    let state = handle.state::<AppState>();
+                    let guard = state.backup_path.lock().unwrap();
+                    match &*guard {
+                        Some(root) => root.clone(),
+                        None => return,
+                    }
+                };
+                // Content-addressed mode: buffer a single coalesced snapshot
+                // request keyed on the watched root and let the timer thread run
+                // the (synchronous) tree walk once the burst settles, keeping the
+                // notify callback fast and collapsing many events into one snapshot.
+                let dedup_backups = handle.state::<AppState>().config.lock().unwrap().dedup_backups;
+                if dedup_backups {
+                    let state = handle.state::<AppState>();
+                    enqueue(&state, watched_root.clone(), PendingAction::Snapshot, false);
+                    return;
+                }
+
+                let backup_folder = Path::new(&dest_root).join("Tether_Backups");
+                if !backup_folder.exists() {
+                    let _ = fs::create_dir_all(&backup_folder);
+                }
+
+                // Buffer the event in the debouncer; the timer thread acts on it
+                // once its path has gone quiet. We keep the notify callback fast.
+                let state = handle.state::<AppState>();
+                match event.kind {
+                    // Rename/move: relocate the mirrored copy rather than re-copying.
+                    notify::EventKind::Modify(ModifyKind::Name(mode)) => {
+                        let _ = handle.emit("file-changed", &paths);
+                        match mode {
+                            RenameMode::Both if paths.len() >= 2 => {
+                                enqueue(&state, paths[1].clone(), PendingAction::Move { from: paths[0].clone() }, false);
+                            }
+                            RenameMode::From => {
+                                for path in &paths {
+                                    enqueue(&state, path.clone(), PendingAction::Remove, false);
+                                }
+                            }
+                            RenameMode::To => {
+                                for path in &paths {
+                                    enqueue(&state, path.clone(), PendingAction::Upsert, true);
+                                }
+                            }
+                            _ => {
+                                if paths.len() >= 2 {
+                                    enqueue(&state, paths[1].clone(), PendingAction::Move { from: paths[0].clone() }, false);
+                                } else {
+                                    for path in &paths {
+                                        if path.exists() {
+                                            enqueue(&state, path.clone(), PendingAction::Upsert, false);
+                                        } else {
+                                            enqueue(&state, path.clone(), PendingAction::Remove, false);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    notify::EventKind::Create(_) => {
+                        let _ = handle.emit("file-changed", &paths);
+                        for path in &paths {
+                            enqueue(&state, path.clone(), PendingAction::Upsert, true);
+                        }
+                    }
+                    notify::EventKind::Modify(_) => {
+                        let _ = handle.emit("file-changed", &paths);
+                        for path in &paths {
+                            enqueue(&state, path.clone(), PendingAction::Upsert, false);
+                        }
+                    }
+                    notify::EventKind::Remove(_) => {
+                        for path in &paths {
+                            enqueue(&state, path.clone(), PendingAction::Remove, false);
+                        }
+                    }
+                    _ => {}
+                }
+            }
             Err(e) => println!("watch error: {:?}", e),
         }
     }).map_err(|e| e.to_string())?;
@@ -204,9 +756,15 @@ pub fn run() {
         .manage(AppState { 
             watcher: Mutex::new(None),
             backup_path: Mutex::new(None),
-            config: Mutex::new(AppConfig::default()), 
+            config: Mutex::new(AppConfig::default()),
+            sync_cache: Mutex::new(HashMap::new()),
+            cache_dirty: std::sync::atomic::AtomicBool::new(false),
+            pending: Mutex::new(HashMap::new()),
+            watched_root: Mutex::new(None),
+            matcher: Mutex::new(patterns::Matcher::from_patterns(default_ignore_patterns())),
+            tetherignore: Mutex::new(Vec::new()),
         })
-        .invoke_handler(tauri::generate_handler![get_drives, start_watching, set_backup_path, get_config, save_config])
+        .invoke_handler(tauri::generate_handler![get_drives, start_watching, set_backup_path, get_config, save_config, list_snapshots, restore_snapshot, garbage_collect])
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
                 window.hide().unwrap();
@@ -224,6 +782,18 @@ pub fn run() {
                    if let Ok(config) = serde_json::from_str::<AppConfig>(&content) {
                        let state = app.state::<AppState>();
                        *state.config.lock().unwrap() = config;
+                       rebuild_matcher(&state);
+                   }
+               }
+            }
+
+            // Load the persisted sync cache (path -> len/mtime/hash) if present.
+            let cache_path = app_dir.join("tether_sync_cache.json");
+            if cache_path.exists() {
+               if let Ok(content) = fs::read_to_string(cache_path) {
+                   if let Ok(cache) = serde_json::from_str::<HashMap<PathBuf, SyncCacheEntry>>(&content) {
+                       let state = app.state::<AppState>();
+                       *state.sync_cache.lock().unwrap() = cache;
                    }
                }
             }
@@ -251,6 +821,17 @@ pub fn run() {
                 })
                 .build(app);
             
+            // Spawn the debouncer's timer thread: it periodically flushes any
+            // buffered events whose path has gone quiet for the debounce window.
+            let debounce_handle = app.handle().clone();
+            thread::spawn(move || {
+                loop {
+                    thread::sleep(Duration::from_millis(100));
+                    flush_ready(&debounce_handle);
+                    flush_sync_cache(&debounce_handle);
+                }
+            });
+
             // Spawn a background thread to monitor drives
             thread::spawn(move || {
                 let mut previous_disks = Disks::new_with_refreshed_list();
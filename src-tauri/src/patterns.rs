@@ -0,0 +1,229 @@
+// A small gitignore-style pattern matcher, in the spirit of the matchers
+// Mercurial/Sapling use for `status` filtering. Patterns are compiled once into
+// a `Matcher` and then consulted per path. Supported syntax:
+//
+//   *.log        floating glob, matches a file of that name at any depth
+//   build/       directory-only, matches any directory named `build`
+//   /src/*.tmp   leading-slash / embedded-slash patterns are anchored to the root
+//   **/gen       `**` spans path separators
+//   !keep.txt    a leading `!` re-includes a previously ignored path
+//   # comment    blank lines and `#` comments are ignored
+//
+// Later patterns win over earlier ones, so a negation can re-include a path that
+// an earlier pattern excluded.
+
+// One compiled glob token.
+#[derive(Clone, Debug)]
+enum Tok {
+    Literal(char),
+    AnyNoSlash,
+    StarNoSlash,
+    DoubleStar,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+    toks: Vec<Tok>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Matcher {
+    rules: Vec<Rule>,
+}
+
+impl Matcher {
+    pub fn from_patterns<I: IntoIterator<Item = String>>(patterns: I) -> Self {
+        let rules = patterns.into_iter().filter_map(|p| parse_rule(&p)).collect();
+        Matcher { rules }
+    }
+
+    // Whether `rel` (a path relative to the watched root, `/`-separated) is
+    // ignored. `is_dir` lets directory-only patterns apply correctly.
+    pub fn is_match(&self, rel: &str, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let normalized = rel.replace('\\', "/");
+        let segs: Vec<&str> = normalized.split('/').filter(|s| !s.is_empty()).collect();
+        if segs.is_empty() {
+            return false;
+        }
+
+        // Anchored patterns are tested against each path prefix so that ignoring a
+        // directory also ignores everything nested under it.
+        let mut prefixes: Vec<(String, bool)> = Vec::with_capacity(segs.len());
+        for i in 0..segs.len() {
+            let prefix = segs[..=i].join("/");
+            let dir = if i < segs.len() - 1 { true } else { is_dir };
+            prefixes.push((prefix, dir));
+        }
+
+        let mut ignored = false;
+        for rule in &self.rules {
+            let matched = if rule.anchored {
+                prefixes
+                    .iter()
+                    .any(|(p, d)| (!rule.dir_only || *d) && glob_matches(&rule.toks, &p.chars().collect::<Vec<_>>()))
+            } else {
+                segs.iter().enumerate().any(|(i, seg)| {
+                    let dir = if i < segs.len() - 1 { true } else { is_dir };
+                    (!rule.dir_only || dir) && glob_matches(&rule.toks, &seg.chars().collect::<Vec<_>>())
+                })
+            };
+            if matched {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let mut s = line.trim();
+    if s.is_empty() || s.starts_with('#') {
+        return None;
+    }
+    let mut negated = false;
+    if let Some(rest) = s.strip_prefix('!') {
+        negated = true;
+        s = rest;
+    }
+    let mut dir_only = false;
+    if s.ends_with('/') {
+        dir_only = true;
+        s = s.trim_end_matches('/');
+    }
+    if s.is_empty() {
+        return None;
+    }
+    // A leading or embedded slash anchors the pattern to the watched root;
+    // otherwise it floats and matches a single path component at any depth.
+    let anchored = s.starts_with('/') || s.contains('/');
+    let s = s.trim_start_matches('/');
+    Some(Rule { negated, dir_only, anchored, toks: tokenize(s) })
+}
+
+fn tokenize(pat: &str) -> Vec<Tok> {
+    let chars: Vec<char> = pat.chars().collect();
+    let mut toks = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                if i + 1 < chars.len() && chars[i + 1] == '*' {
+                    toks.push(Tok::DoubleStar);
+                    i += 2;
+                } else {
+                    toks.push(Tok::StarNoSlash);
+                    i += 1;
+                }
+            }
+            '?' => {
+                toks.push(Tok::AnyNoSlash);
+                i += 1;
+            }
+            c => {
+                toks.push(Tok::Literal(c));
+                i += 1;
+            }
+        }
+    }
+    toks
+}
+
+// Backtracking glob match. `*`/`?` never cross a `/`; `**` may.
+fn glob_matches(toks: &[Tok], s: &[char]) -> bool {
+    match toks.split_first() {
+        None => s.is_empty(),
+        Some((tok, rest)) => match tok {
+            Tok::Literal(c) => !s.is_empty() && s[0] == *c && glob_matches(rest, &s[1..]),
+            Tok::AnyNoSlash => !s.is_empty() && s[0] != '/' && glob_matches(rest, &s[1..]),
+            Tok::StarNoSlash => {
+                if glob_matches(rest, s) {
+                    return true;
+                }
+                !s.is_empty() && s[0] != '/' && glob_matches(toks, &s[1..])
+            }
+            Tok::DoubleStar => {
+                if glob_matches(rest, s) {
+                    return true;
+                }
+                !s.is_empty() && glob_matches(toks, &s[1..])
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(pats: &[&str]) -> Matcher {
+        Matcher::from_patterns(pats.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn floating_glob_matches_at_any_depth() {
+        let m = matcher(&["*.log"]);
+        assert!(m.is_match("debug.log", false));
+        assert!(m.is_match("src/nested/debug.log", false));
+        assert!(!m.is_match("debug.txt", false));
+    }
+
+    #[test]
+    fn dir_only_pattern_needs_a_directory() {
+        let m = matcher(&["build/"]);
+        assert!(m.is_match("build", true));
+        // A directory match also excludes everything nested under it.
+        assert!(m.is_match("build/out.o", false));
+        // A plain file named `build` is not a directory, so it is not excluded.
+        assert!(!m.is_match("build", false));
+    }
+
+    #[test]
+    fn negation_reincludes_and_respects_order() {
+        let m = matcher(&["*.txt", "!keep.txt"]);
+        assert!(m.is_match("notes.txt", false));
+        assert!(!m.is_match("keep.txt", false));
+
+        // Order matters: a later broad ignore overrides an earlier negation.
+        let m = matcher(&["!keep.txt", "*.txt"]);
+        assert!(m.is_match("keep.txt", false));
+    }
+
+    #[test]
+    fn anchored_vs_floating() {
+        // Embedded slash anchors to the root.
+        let m = matcher(&["src/*.tmp"]);
+        assert!(m.is_match("src/a.tmp", false));
+        assert!(!m.is_match("lib/src/a.tmp", false));
+
+        // No slash floats and matches a single component anywhere.
+        let m = matcher(&["target"]);
+        assert!(m.is_match("a/target/b.o", false));
+    }
+
+    #[test]
+    fn double_star_spans_separators() {
+        // `**` stands in for one or more intervening path components.
+        let m = matcher(&["src/**/gen"]);
+        assert!(m.is_match("src/x/gen", true));
+        assert!(m.is_match("src/a/b/gen", true));
+        assert!(!m.is_match("src/a/b/genx", true));
+    }
+
+    #[test]
+    fn empty_matcher_ignores_nothing() {
+        let m = matcher(&[]);
+        assert!(!m.is_match("anything.log", false));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let m = matcher(&["# a comment", "", "*.log"]);
+        assert!(m.is_match("x.log", false));
+    }
+}
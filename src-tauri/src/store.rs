@@ -0,0 +1,378 @@
+// Content-addressed deduplicating backup store, modeled on Proxmox Backup's
+// datastore: file data is split into fixed-size chunks named by their hash and
+// written once under `chunks/`, so repeated or lightly-edited files share
+// storage. Each sync is recorded as a snapshot manifest under
+// `snapshots/<id>/manifest.json`, giving point-in-time restore and
+// deduplication across versions.
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// One file inside a snapshot: its path relative to the watched root, the ordered
+// list of chunk hashes that reconstruct it, and its size/mtime at capture time.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileEntry {
+    pub path: String,
+    pub chunks: Vec<String>,
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Manifest {
+    pub files: Vec<FileEntry>,
+}
+
+// Summary of a snapshot, returned to the frontend by `list_snapshots`.
+#[derive(Serialize, Clone, Debug)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub file_count: usize,
+    pub total_size: u64,
+}
+
+fn chunks_dir(store: &Path) -> PathBuf {
+    store.join("chunks")
+}
+
+fn snapshots_dir(store: &Path) -> PathBuf {
+    store.join("snapshots")
+}
+
+fn mtime_parts(meta: &fs::Metadata) -> (u64, u32) {
+    match meta.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()) {
+        Some(d) => (d.as_secs(), d.subsec_nanos()),
+        None => (0, 0),
+    }
+}
+
+// Fill `buf` by reading until it is full or EOF; returns the number of bytes read
+// so short reads don't create spurious chunk boundaries.
+fn read_chunk(file: &mut fs::File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = file.read(&mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+// Write a chunk into the store if it isn't already present and return its hash.
+// The write goes through a temp file + rename so a crash never leaves a partial
+// chunk under its final (content-addressed) name.
+fn store_chunk(store: &Path, data: &[u8]) -> std::io::Result<String> {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    let hash = format!("{:x}", hasher.finalize());
+
+    let dir = chunks_dir(store);
+    fs::create_dir_all(&dir)?;
+    let target = dir.join(&hash);
+    if !target.exists() {
+        let tmp = dir.join(format!(".{}.tmp", hash));
+        fs::write(&tmp, data)?;
+        if fs::rename(&tmp, &target).is_err() {
+            let _ = fs::copy(&tmp, &target);
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+    Ok(hash)
+}
+
+// Chunk a single file into the store, returning its manifest entry.
+fn store_file(store: &Path, rel: &str, path: &Path) -> std::io::Result<FileEntry> {
+    let meta = path.metadata()?;
+    let mut file = fs::File::open(path)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut chunks = Vec::new();
+    loop {
+        let n = read_chunk(&mut file, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        chunks.push(store_chunk(store, &buf[..n])?);
+    }
+    let (mtime_secs, mtime_nanos) = mtime_parts(&meta);
+    Ok(FileEntry { path: rel.to_string(), chunks, size: meta.len(), mtime_secs, mtime_nanos })
+}
+
+// Recursively collect regular files under `dir`, skipping anything the ignore
+// matcher excludes, recording each as (relative-path, absolute-path).
+fn collect_files(root: &Path, dir: &Path, matcher: &crate::patterns::Matcher, out: &mut Vec<(String, PathBuf)>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let rel = match path.strip_prefix(root) {
+            Ok(rel) => rel.to_string_lossy().replace('\\', "/"),
+            Err(_) => continue,
+        };
+        if matcher.is_match(&rel, path.is_dir()) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, matcher, out);
+        } else if path.is_file() {
+            out.push((rel, path.clone()));
+        }
+    }
+}
+
+// Capture the watched tree as a new snapshot and return its id. Shared chunks are
+// written only once, so successive snapshots of a slowly-changing folder cost
+// only the bytes that actually changed.
+pub fn snapshot(store: &Path, watched_root: &Path, matcher: &crate::patterns::Matcher, id: &str) -> std::io::Result<String> {
+    let mut files = Vec::new();
+    collect_files(watched_root, watched_root, matcher, &mut files);
+
+    // Reuse the previous snapshot's entries for files whose size and mtime are
+    // unchanged, so an unchanged tree is re-hashed only for what actually moved
+    // rather than re-read in full — the same len/mtime signal the flat-copy path
+    // uses to skip work. The store already deduplicates chunk *writes*; this
+    // avoids the chunk *reads* too, which is what hurts on slow/removable media.
+    let (prev_secs, previous): (u64, std::collections::HashMap<String, FileEntry>) =
+        match latest_manifest(store) {
+            Some((id, m)) => (
+                snapshot_secs(&id),
+                m.files.into_iter().map(|f| (f.path.clone(), f)).collect(),
+            ),
+            None => (0, std::collections::HashMap::new()),
+        };
+
+    let mut entries = Vec::with_capacity(files.len());
+    for (rel, abs) in &files {
+        if let (Ok(meta), Some(prev)) = (abs.metadata(), previous.get(rel)) {
+            let (secs, nanos) = mtime_parts(&meta);
+            // Only trust the carried-forward chunks when the stored mtime is
+            // unambiguous: a same-second, size-preserving in-place edit could hide
+            // a change, so fall through and re-chunk rather than record stale data.
+            if prev.size == meta.len()
+                && prev.mtime_secs == secs
+                && prev.mtime_nanos == nanos
+                && crate::mtime_is_reliable(secs, nanos, prev_secs)
+            {
+                entries.push(prev.clone());
+                continue;
+            }
+        }
+        if let Ok(entry) = store_file(store, rel, abs) {
+            entries.push(entry);
+        }
+    }
+    let manifest = Manifest { files: entries };
+
+    let snap_dir = snapshots_dir(store).join(id);
+    fs::create_dir_all(&snap_dir)?;
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    let tmp = snap_dir.join(".manifest.json.tmp");
+    fs::write(&tmp, json)?;
+    let final_path = snap_dir.join("manifest.json");
+    if fs::rename(&tmp, &final_path).is_err() {
+        let _ = fs::copy(&tmp, &final_path);
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(id.to_string())
+}
+
+fn read_manifest(store: &Path, id: &str) -> std::io::Result<Manifest> {
+    let path = snapshots_dir(store).join(id).join("manifest.json");
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+// The most recent snapshot's id and manifest, if any. Ids are timestamp-ordered,
+// so the lexically greatest directory is the newest. Used to reuse the chunk
+// lists of files that haven't changed since the last snapshot.
+fn latest_manifest(store: &Path) -> Option<(String, Manifest)> {
+    let mut ids: Vec<String> = fs::read_dir(snapshots_dir(store))
+        .ok()?
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    ids.sort();
+    let id = ids.pop()?;
+    read_manifest(store, &id).ok().map(|m| (id, m))
+}
+
+// The wall-clock second a snapshot was captured, parsed from its id (see
+// `snapshot_id` in lib.rs: "<secs>.<nanos>-<n>"). Used, like the flat-copy path's
+// `synced_at_secs`, to detect second-ambiguity against a file's stored mtime.
+fn snapshot_secs(id: &str) -> u64 {
+    id.split('.').next().and_then(|s| s.parse().ok()).unwrap_or(0)
+}
+
+// List all snapshots, newest first (ids are timestamp-ordered).
+pub fn list_snapshots(store: &Path) -> Vec<SnapshotInfo> {
+    let mut out = Vec::new();
+    let entries = match fs::read_dir(snapshots_dir(store)) {
+        Ok(entries) => entries,
+        Err(_) => return out,
+    };
+    for entry in entries.flatten() {
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let id = entry.file_name().to_string_lossy().to_string();
+        if let Ok(manifest) = read_manifest(store, &id) {
+            let total_size = manifest.files.iter().map(|f| f.size).sum();
+            out.push(SnapshotInfo { id, file_count: manifest.files.len(), total_size });
+        }
+    }
+    out.sort_by(|a, b| b.id.cmp(&a.id));
+    out
+}
+
+// Reconstruct every file in a snapshot under `dest`, rebuilding each from its
+// chunks. Files are written through a temp file + rename so a restore is atomic
+// per file.
+pub fn restore_snapshot(store: &Path, id: &str, dest: &Path) -> std::io::Result<()> {
+    let manifest = read_manifest(store, id)?;
+    let chunks = chunks_dir(store);
+    for file in &manifest.files {
+        let out_path = dest.join(&file.path);
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = out_path.with_extension("tether-restore-tmp");
+        {
+            let mut writer = fs::File::create(&tmp)?;
+            for hash in &file.chunks {
+                let data = fs::read(chunks.join(hash))?;
+                use std::io::Write;
+                writer.write_all(&data)?;
+            }
+            writer.sync_all()?;
+        }
+        if fs::rename(&tmp, &out_path).is_err() {
+            let _ = fs::copy(&tmp, &out_path);
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+    Ok(())
+}
+
+// Sweep chunks that no snapshot manifest references, returning the number removed.
+pub fn garbage_collect(store: &Path) -> std::io::Result<usize> {
+    let mut referenced: HashSet<String> = HashSet::new();
+    if let Ok(entries) = fs::read_dir(snapshots_dir(store)) {
+        for entry in entries.flatten() {
+            if !entry.path().is_dir() {
+                continue;
+            }
+            let id = entry.file_name().to_string_lossy().to_string();
+            if let Ok(manifest) = read_manifest(store, &id) {
+                for file in &manifest.files {
+                    referenced.extend(file.chunks.iter().cloned());
+                }
+            }
+        }
+    }
+
+    let mut removed = 0;
+    if let Ok(entries) = fs::read_dir(chunks_dir(store)) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Leave temp files alone; they belong to an in-flight write.
+            if name.starts_with('.') {
+                continue;
+            }
+            if !referenced.contains(&name) {
+                if fs::remove_file(entry.path()).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Matcher;
+
+    // A unique scratch directory under the system temp dir, cleaned up by the
+    // caller. We avoid a temp-dir crate to match the repo's dependency-light style.
+    fn scratch(tag: &str) -> PathBuf {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("tether-store-test-{}-{}", std::process::id(), n));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> Vec<u8> {
+        fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn snapshot_restore_roundtrip_and_gc() {
+        let base = scratch("roundtrip");
+        let source = base.join("source");
+        let store = base.join("store");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        // A file larger than one chunk plus a nested one, so we exercise multiple
+        // chunks and the relative-path handling.
+        fs::write(source.join("big.bin"), vec![7u8; CHUNK_SIZE * 2 + 3]).unwrap();
+        fs::write(source.join("sub/note.txt"), b"hello tether").unwrap();
+
+        let matcher = Matcher::from_patterns(Vec::<String>::new());
+        let id = snapshot(&store, &source, &matcher, "0000000001.000000000-0000").unwrap();
+
+        let snaps = list_snapshots(&store);
+        assert_eq!(snaps.len(), 1);
+        assert_eq!(snaps[0].id, id);
+        assert_eq!(snaps[0].file_count, 2);
+
+        // Restore to a fresh destination and confirm the bytes match exactly.
+        let dest = base.join("restored");
+        restore_snapshot(&store, &id, &dest).unwrap();
+        assert_eq!(read(&dest.join("big.bin")), vec![7u8; CHUNK_SIZE * 2 + 3]);
+        assert_eq!(read(&dest.join("sub/note.txt")), b"hello tether");
+
+        // Every chunk is still referenced by the snapshot, so gc removes nothing.
+        assert_eq!(garbage_collect(&store).unwrap(), 0);
+
+        // Drop the snapshot manifest and gc should sweep its now-orphaned chunks.
+        fs::remove_dir_all(snapshots_dir(&store).join(&id)).unwrap();
+        let removed = garbage_collect(&store).unwrap();
+        assert!(removed >= 3, "expected orphaned chunks swept, got {}", removed);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn unchanged_files_reuse_previous_chunks() {
+        let base = scratch("reuse");
+        let source = base.join("source");
+        let store = base.join("store");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), b"stable contents").unwrap();
+
+        let matcher = Matcher::from_patterns(Vec::<String>::new());
+        let first = snapshot(&store, &source, &matcher, "0000000001.000000000-0000").unwrap();
+        let second = snapshot(&store, &source, &matcher, "0000000002.000000000-0000").unwrap();
+
+        // The unchanged file must carry identical chunk hashes across snapshots.
+        let m1 = read_manifest(&store, &first).unwrap();
+        let m2 = read_manifest(&store, &second).unwrap();
+        assert_eq!(m1.files[0].chunks, m2.files[0].chunks);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}